@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str;
 
 use ruby_prism::{Node, Visit};
@@ -7,6 +8,9 @@ use ruby_prism::{Node, Visit};
 pub enum Type {
     Signature(HashMap<String, Method>),
     Alias(String),
+    /// The class or module object itself, e.g. `Foo` in `Foo.new`.
+    Singleton(String),
+    Union(Vec<Type>),
 }
 
 impl Type {
@@ -14,6 +18,10 @@ impl Type {
         Self::Alias(name.to_string())
     }
 
+    pub fn singleton(name: &str) -> Self {
+        Self::Singleton(name.to_string())
+    }
+
     pub fn sig<I>(iter: I) -> Self
     where
         I: IntoIterator<Item = (String, Method)>,
@@ -21,6 +29,56 @@ impl Type {
         Self::Signature(HashMap::from_iter(iter))
     }
 
+    /// Joins `types` into one type, flattening nested unions and dropping
+    /// duplicates. An empty union is `NilClass`.
+    pub fn union<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Type>,
+    {
+        let mut types = Vec::new();
+        for ty in iter {
+            let members = match ty {
+                Self::Union(members) => members,
+                ty => vec![ty],
+            };
+            for member in members {
+                if !types.contains(&member) {
+                    types.push(member);
+                }
+            }
+        }
+        match types.len() {
+            0 => Self::alias("NilClass"),
+            1 => types.pop().unwrap(),
+            _ => Self::Union(types),
+        }
+    }
+
+    /// Whether a value of type `actual` may be passed where `self` is
+    /// expected. `Object` stands for an unknown type and accepts anything.
+    fn accepts(&self, actual: &Type) -> bool {
+        let object = Type::alias("Object");
+        if *self == object || *actual == object || self == actual {
+            return true;
+        }
+        match actual {
+            Self::Union(types) => types.iter().any(|ty| self.accepts(ty)),
+            _ => false,
+        }
+    }
+
+    /// Splits the members of this type into those that are always truthy and
+    /// those that are `nil` or `false`.
+    fn split_falsy(self) -> (Vec<Type>, Vec<Type>) {
+        let members = match self {
+            Self::Union(members) => members,
+            ty => vec![ty],
+        };
+        members
+            .into_iter()
+            .partition(|ty| *ty != Type::alias("NilClass") && *ty != Type::alias("FalseClass"))
+    }
+
     pub fn as_sig(&self) -> Option<&HashMap<String, Method>> {
         match self {
             Self::Signature(sig) => Some(sig),
@@ -36,6 +94,24 @@ impl Type {
     }
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Signature(sig) => {
+                let mut names = sig.keys().map(String::as_str).collect::<Vec<_>>();
+                names.sort_unstable();
+                write!(f, "{{{}}}", names.join(", "))
+            }
+            Self::Alias(name) => write!(f, "{}", name),
+            Self::Singleton(name) => write!(f, "singleton({})", name),
+            Self::Union(types) => {
+                let names = types.iter().map(Type::to_string).collect::<Vec<_>>();
+                write!(f, "{}", names.join(" | "))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Method {
     args: Vec<Type>,
@@ -51,6 +127,24 @@ impl Method {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorKind {
     UndefinedVariable(String),
+    UndefinedConstant(String),
+    ReturnOutsideMethod,
+    TypeMismatch { expected: Type, actual: Type },
+    SyntaxError(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UndefinedVariable(name) => write!(f, "undefined variable `{}`", name),
+            Self::UndefinedConstant(name) => write!(f, "undefined constant `{}`", name),
+            Self::ReturnOutsideMethod => write!(f, "return outside of a method"),
+            Self::TypeMismatch { expected, actual } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, actual)
+            }
+            Self::SyntaxError(message) => write!(f, "syntax error: {}", message),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -67,15 +161,285 @@ impl TypeError {
             loc: (loc.start_offset(), loc.end_offset()),
         }
     }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn loc(&self) -> (usize, usize) {
+        self.loc
+    }
+
+    pub fn line_column(&self, source: &str) -> (u32, usize, u32, usize) {
+        let (start_line, start_column) = line_column(source, self.loc.0);
+        let (end_line, end_column) = line_column(source, self.loc.1);
+        (start_line, start_column, end_line, end_column)
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (u32, usize) {
+    // Offsets that fall inside a multi-byte character count as that character.
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line = before.matches('\n').count() as u32 + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Classes and modules of the core library that are always defined.
+const CORE_CONSTANTS: &[&str] = &[
+    "ArgumentError",
+    "Array",
+    "BasicObject",
+    "Class",
+    "Comparable",
+    "Complex",
+    "Data",
+    "Dir",
+    "EOFError",
+    "Encoding",
+    "EncodingError",
+    "Enumerable",
+    "Enumerator",
+    "Errno",
+    "Exception",
+    "FalseClass",
+    "Fiber",
+    "FiberError",
+    "File",
+    "Float",
+    "FloatDomainError",
+    "FrozenError",
+    "GC",
+    "Hash",
+    "IO",
+    "IOError",
+    "IndexError",
+    "Integer",
+    "Interrupt",
+    "Kernel",
+    "KeyError",
+    "LoadError",
+    "LocalJumpError",
+    "Marshal",
+    "MatchData",
+    "Math",
+    "Method",
+    "Module",
+    "Mutex",
+    "NameError",
+    "NilClass",
+    "NoMatchingPatternError",
+    "NoMemoryError",
+    "NoMethodError",
+    "NotImplementedError",
+    "Numeric",
+    "Object",
+    "ObjectSpace",
+    "Proc",
+    "Process",
+    "Queue",
+    "Random",
+    "Range",
+    "RangeError",
+    "Rational",
+    "Regexp",
+    "RegexpError",
+    "RuntimeError",
+    "ScriptError",
+    "SecurityError",
+    "Set",
+    "Signal",
+    "SignalException",
+    "StandardError",
+    "StopIteration",
+    "String",
+    "Struct",
+    "Symbol",
+    "SystemCallError",
+    "SystemExit",
+    "SystemStackError",
+    "Thread",
+    "ThreadError",
+    "Time",
+    "TrueClass",
+    "TypeError",
+    "UnboundMethod",
+    "UncaughtThrowError",
+    "ZeroDivisionError",
+];
+
+/// Core constants that hold values rather than classes, with their types.
+const CORE_VALUES: &[(&str, &str)] = &[
+    ("ARGF", "Object"),
+    ("ARGV", "Array"),
+    ("ENV", "Object"),
+    ("RUBY_COPYRIGHT", "String"),
+    ("RUBY_DESCRIPTION", "String"),
+    ("RUBY_ENGINE", "String"),
+    ("RUBY_PATCHLEVEL", "Integer"),
+    ("RUBY_PLATFORM", "String"),
+    ("RUBY_RELEASE_DATE", "String"),
+    ("RUBY_VERSION", "String"),
+    ("STDERR", "Object"),
+    ("STDIN", "Object"),
+    ("STDOUT", "Object"),
+];
+
+/// Global variables that Ruby always sets, with their types.
+const BUILTIN_GLOBALS: &[(&str, &str)] = &[
+    ("$*", "Array"),
+    ("$0", "String"),
+    ("$:", "Array"),
+    ("$>", "IO"),
+    ("$LOADED_FEATURES", "Array"),
+    ("$LOAD_PATH", "Array"),
+    ("$PROGRAM_NAME", "String"),
+    ("$\"", "Array"),
+    ("$stderr", "IO"),
+    ("$stdin", "IO"),
+    ("$stdout", "IO"),
+];
+
+/// The type of a core library constant named `name`, if there is one.
+fn core_constant(name: &str) -> Option<Type> {
+    if CORE_CONSTANTS.contains(&name) {
+        return Some(Type::singleton(name));
+    }
+    CORE_VALUES
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, ty)| Type::alias(ty))
+}
+
+/// Collects the full names of the classes, modules and constants defined in a
+/// program, and the class variables each class writes, before it is checked.
+#[derive(Default)]
+struct Declarations {
+    namespace: Vec<String>,
+    constants: HashMap<String, Type>,
+    class_variables: HashMap<String, HashSet<String>>,
+}
+
+impl Declarations {
+    fn declare_class_variable(&mut self, name: ruby_prism::ConstantId) {
+        let class = self.namespace.last().map_or("#main", String::as_str);
+        self.class_variables
+            .entry(class.to_string())
+            .or_default()
+            .insert(to_string(name));
+    }
+}
+
+impl<'pr> Visit<'pr> for Declarations {
+    fn visit_class_node(&mut self, node: &ruby_prism::ClassNode<'pr>) {
+        let name = qualify(&self.namespace, &node.constant_path());
+        self.constants.insert(name.clone(), Type::singleton(&name));
+        self.namespace.push(name);
+        ruby_prism::visit_class_node(self, node);
+        self.namespace.pop();
+    }
+
+    fn visit_module_node(&mut self, node: &ruby_prism::ModuleNode<'pr>) {
+        let name = qualify(&self.namespace, &node.constant_path());
+        self.constants.insert(name.clone(), Type::singleton(&name));
+        self.namespace.push(name);
+        ruby_prism::visit_module_node(self, node);
+        self.namespace.pop();
+    }
+
+    fn visit_constant_write_node(&mut self, node: &ruby_prism::ConstantWriteNode<'pr>) {
+        let name = nest(&self.namespace, to_string(node.name()));
+        self.constants
+            .entry(name)
+            .or_insert_with(|| Type::alias("Object"));
+        ruby_prism::visit_constant_write_node(self, node);
+    }
+
+    fn visit_constant_path_write_node(&mut self, node: &ruby_prism::ConstantPathWriteNode<'pr>) {
+        let name = qualify(&self.namespace, &node.target().as_node());
+        self.constants
+            .entry(name)
+            .or_insert_with(|| Type::alias("Object"));
+        ruby_prism::visit_constant_path_write_node(self, node);
+    }
+
+    fn visit_constant_path_or_write_node(
+        &mut self,
+        node: &ruby_prism::ConstantPathOrWriteNode<'pr>,
+    ) {
+        let name = qualify(&self.namespace, &node.target().as_node());
+        self.constants
+            .entry(name)
+            .or_insert_with(|| Type::alias("Object"));
+        ruby_prism::visit_constant_path_or_write_node(self, node);
+    }
+
+    fn visit_class_variable_write_node(&mut self, node: &ruby_prism::ClassVariableWriteNode<'pr>) {
+        self.declare_class_variable(node.name());
+        ruby_prism::visit_class_variable_write_node(self, node);
+    }
+
+    fn visit_class_variable_or_write_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableOrWriteNode<'pr>,
+    ) {
+        self.declare_class_variable(node.name());
+        ruby_prism::visit_class_variable_or_write_node(self, node);
+    }
+
+    fn visit_class_variable_target_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableTargetNode<'pr>,
+    ) {
+        self.declare_class_variable(node.name());
+    }
+}
+
+enum Variable {
+    /// A local variable and its scope depth.
+    Local(String, u32),
+    Instance(String),
+    Class(String),
+    Global(String),
+    /// A constant by its full name.
+    Constant(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypeChecker {
     types: HashMap<String, Type>,
     objects: HashMap<String, Type>,
+    modules: HashMap<String, Type>,
+    /// Singleton methods of each class or module, keyed by its name.
+    singletons: HashMap<String, Type>,
     type_stack: Vec<Type>,
     local_variables: Vec<HashMap<String, Type>>,
-    params: Vec<String>,
+    /// Instance variables of each object, keyed by the object's name.
+    instance_variables: HashMap<String, HashMap<String, Type>>,
+    /// Class variables of each class, keyed by the lexically enclosing class
+    /// so that singleton and instance methods share them.
+    class_variables: HashMap<String, HashMap<String, Type>>,
+    globals: HashMap<String, Type>,
+    constants: HashMap<String, Type>,
+    /// Every class, module and constant defined anywhere in the program, so
+    /// that references may come before definitions.
+    declared_constants: HashMap<String, Type>,
+    /// Class variables written anywhere in each class, so that a method may
+    /// read one that a later method writes.
+    declared_class_variables: HashMap<String, HashSet<String>>,
+    namespace: Vec<String>,
+    /// The current `self`.
+    object_stack: Vec<Type>,
+    /// The object that a plain `def` adds methods to, which in a class body
+    /// is the class's instances rather than `self`. `None` inside
+    /// `class << obj` for anything but `self`, whose methods are not tracked.
+    definees: Vec<Option<Type>>,
+    return_types: Vec<Vec<Type>>,
     errors: Vec<TypeError>,
 }
 
@@ -86,27 +450,457 @@ impl TypeChecker {
         let mut types = HashMap::new();
         types.insert(
             "String".to_string(),
-            Type::sig([(
-                "upcase".to_string(),
-                Method {
-                    args: vec![],
-                    ret: Type::alias("String"),
-                },
-            )]),
+            Type::sig([
+                (
+                    "upcase".to_string(),
+                    Method::new(vec![], Type::alias("String")),
+                ),
+                (
+                    "downcase".to_string(),
+                    Method::new(vec![], Type::alias("String")),
+                ),
+                (
+                    "reverse".to_string(),
+                    Method::new(vec![], Type::alias("String")),
+                ),
+                (
+                    "strip".to_string(),
+                    Method::new(vec![], Type::alias("String")),
+                ),
+                (
+                    "length".to_string(),
+                    Method::new(vec![], Type::alias("Integer")),
+                ),
+                (
+                    "+".to_string(),
+                    Method::new(vec![Type::alias("String")], Type::alias("String")),
+                ),
+            ]),
         );
         Self {
             types,
             objects,
+            modules: HashMap::new(),
+            singletons: HashMap::new(),
             type_stack: Vec::new(),
-            local_variables: Vec::new(),
-            params: Vec::new(),
+            local_variables: vec![HashMap::new()],
+            instance_variables: HashMap::new(),
+            class_variables: HashMap::new(),
+            globals: BUILTIN_GLOBALS
+                .iter()
+                .map(|(name, ty)| (name.to_string(), Type::alias(ty)))
+                .collect(),
+            constants: HashMap::new(),
+            declared_constants: HashMap::new(),
+            declared_class_variables: HashMap::new(),
+            namespace: Vec::new(),
+            object_stack: vec![Type::alias("#main")],
+            definees: vec![Some(Type::alias("#main"))],
+            return_types: Vec::new(),
             errors: Vec::new(),
         }
     }
 
+    /// Parses and checks `source`. Syntax errors reported by the parser come
+    /// first, followed by the errors found while checking the recovered tree.
+    pub fn check(source: &str) -> Vec<TypeError> {
+        let parse_result = ruby_prism::parse(source.as_bytes());
+        let mut errors = parse_result
+            .errors()
+            .map(|diagnostic| {
+                let loc = diagnostic.location();
+                TypeError {
+                    kind: ErrorKind::SyntaxError(diagnostic.message().to_string()),
+                    loc: (loc.start_offset(), loc.end_offset()),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut checker = Self::new();
+        checker.visit(&parse_result.node());
+        errors.extend(checker.into_errors());
+        errors
+    }
+
     pub fn get_object(&self, name: &str) -> Option<&Type> {
         self.objects.get(name)
     }
+
+    pub fn get_module(&self, name: &str) -> Option<&Type> {
+        self.modules.get(name)
+    }
+
+    pub fn get_singleton(&self, name: &str) -> Option<&Type> {
+        self.singletons.get(name)
+    }
+
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<TypeError> {
+        self.errors
+    }
+
+    fn pop_type(&mut self) -> Type {
+        self.type_stack
+            .pop()
+            .unwrap_or_else(|| Type::alias("NilClass"))
+    }
+
+    /// Visits an expression and returns its type, or `None` when the
+    /// expression does not produce exactly one known type.
+    fn value_type<'pr>(&mut self, node: &Node<'pr>) -> Option<Type> {
+        let stack_depth = self.type_stack.len();
+        self.visit(node);
+        let ty = if self.type_stack.len() == stack_depth + 1 {
+            self.type_stack.pop()
+        } else {
+            None
+        };
+        self.type_stack.truncate(stack_depth);
+        ty
+    }
+
+    /// Checks the arguments of a call against the method found on `receiver`
+    /// and returns its return type. Arguments of unknown type are not checked.
+    fn call_method<'pr>(
+        &mut self,
+        receiver: &Type,
+        name: &str,
+        args: Vec<(Option<Type>, Node<'pr>)>,
+    ) -> Option<Type> {
+        let method = self.find_method(receiver, name)?.clone();
+        for (expected, (actual, argument)) in method.args.iter().zip(args) {
+            let Some(actual) = actual else {
+                continue;
+            };
+            if !expected.accepts(&actual) {
+                self.errors.push(TypeError::new(
+                    ErrorKind::TypeMismatch {
+                        expected: expected.clone(),
+                        actual,
+                    },
+                    argument,
+                ));
+            }
+        }
+        Some(method.ret)
+    }
+
+    fn find_method<'a>(&'a self, receiver: &'a Type, name: &str) -> Option<&'a Method> {
+        let sig = match receiver {
+            Type::Signature(sig) => sig,
+            Type::Alias(alias) => self
+                .types
+                .get(alias)
+                .or_else(|| self.objects.get(alias))
+                .or_else(|| self.modules.get(alias))?
+                .as_sig()?,
+            Type::Singleton(name) => self.singletons.get(name)?.as_sig()?,
+            Type::Union(_) => return None,
+        };
+        sig.get(name)
+    }
+
+    fn variable_type(&self, variable: &Variable) -> Option<Type> {
+        let ty = match variable {
+            // `depth` counts scopes upwards as prism does: each block adds a
+            // scope that can see its parent.
+            Variable::Local(name, depth) => self
+                .local_variables
+                .iter()
+                .rev()
+                .nth(*depth as usize)?
+                .get(name),
+            Variable::Instance(name) => self
+                .instance_variables
+                .get(&self.object_stack.last().unwrap().to_string())?
+                .get(name),
+            Variable::Class(name) => self.class_variables.get(self.class_scope())?.get(name),
+            Variable::Global(name) => self.globals.get(name),
+            Variable::Constant(name) => return self.lookup_constant(name),
+        };
+        ty.cloned()
+    }
+
+    fn set_variable(&mut self, variable: Variable, ty: Type) {
+        match variable {
+            Variable::Local(name, depth) => {
+                if let Some(scope) = self.local_variables.iter_mut().rev().nth(depth as usize) {
+                    scope.insert(name, ty);
+                }
+            }
+            Variable::Instance(name) => {
+                let object = self.object_stack.last().unwrap().to_string();
+                self.instance_variables
+                    .entry(object)
+                    .or_default()
+                    .insert(name, ty);
+            }
+            Variable::Class(name) => {
+                self.class_variables
+                    .entry(self.class_scope().to_string())
+                    .or_default()
+                    .insert(name, ty);
+            }
+            Variable::Global(name) => {
+                self.globals.insert(name, ty);
+            }
+            Variable::Constant(name) => {
+                self.constants.insert(name, ty);
+            }
+        }
+    }
+
+    /// Binds a parameter in the current scope. Anonymous parameters such as
+    /// a bare `*` have no name and bind nothing.
+    fn bind_parameter(&mut self, name: Option<ruby_prism::ConstantId>, ty: &str) {
+        if let Some(name) = name {
+            self.set_variable(Variable::Local(to_string(name), 0), Type::alias(ty));
+        }
+    }
+
+    fn write_variable<'pr>(&mut self, variable: Variable, value: &Node<'pr>) {
+        let ty = self
+            .value_type(value)
+            .unwrap_or_else(|| Type::alias("Object"));
+        self.set_variable(variable, ty.clone());
+        self.type_stack.push(ty);
+    }
+
+    /// `a op= b` calls `op` on the current value, so the value is checked
+    /// against the operator's parameter like any other argument.
+    fn operator_write_variable<'pr>(
+        &mut self,
+        variable: Variable,
+        operator: ruby_prism::ConstantId,
+        value: Node<'pr>,
+    ) {
+        let current = self
+            .variable_type(&variable)
+            .unwrap_or_else(|| Type::alias("NilClass"));
+        let value_type = self.value_type(&value);
+        let ty = self
+            .call_method(&current, &to_string(operator), vec![(value_type, value)])
+            .unwrap_or(current);
+        self.set_variable(variable, ty.clone());
+        self.type_stack.push(ty);
+    }
+
+    fn or_write_variable<'pr>(&mut self, variable: Variable, value: &Node<'pr>) {
+        let current = self.variable_type(&variable);
+        let value = self
+            .value_type(value)
+            .unwrap_or_else(|| Type::alias("Object"));
+        // A variable that has not been assigned yet is nil.
+        let ty = match current.map(|current| current.split_falsy()) {
+            Some((truthy, falsy)) if falsy.is_empty() => Type::union(truthy),
+            Some((truthy, _)) => Type::union(truthy.into_iter().chain([value])),
+            None => value,
+        };
+        self.set_variable(variable, ty.clone());
+        self.type_stack.push(ty);
+    }
+
+    fn and_write_variable<'pr>(&mut self, variable: Variable, value: &Node<'pr>) {
+        let current = self.variable_type(&variable);
+        let value = self
+            .value_type(value)
+            .unwrap_or_else(|| Type::alias("Object"));
+        let ty = match current.map(|current| current.split_falsy()) {
+            Some((truthy, falsy)) if truthy.is_empty() => Type::union(falsy),
+            Some((_, falsy)) => Type::union([value].into_iter().chain(falsy)),
+            None => Type::alias("NilClass"),
+        };
+        self.set_variable(variable, ty.clone());
+        self.type_stack.push(ty);
+    }
+
+    /// Visits a class or module body with `name` as the lexical namespace and
+    /// the current object, in a fresh local scope.
+    fn visit_namespace_body<'pr>(&mut self, name: String, body: Option<Node<'pr>>) {
+        let stack_depth = self.type_stack.len();
+        // A class body is never inside a method, even when nested in one.
+        let return_types = std::mem::take(&mut self.return_types);
+        self.namespace.push(name.clone());
+        self.object_stack.push(Type::Singleton(name.clone()));
+        self.definees.push(Some(Type::Alias(name)));
+        self.local_variables.push(HashMap::new());
+        if let Some(body) = body {
+            self.visit(&body);
+        }
+        self.local_variables.pop();
+        self.definees.pop();
+        self.object_stack.pop();
+        self.namespace.pop();
+        self.return_types = return_types;
+        self.type_stack.truncate(stack_depth);
+    }
+
+    /// The singleton of the current `self`, e.g. `singleton(Foo)` both in the
+    /// body of `class Foo` and in its instance methods.
+    fn singleton_of_self(&self) -> Type {
+        match self.object_stack.last().unwrap() {
+            Type::Alias(name) | Type::Singleton(name) => Type::Singleton(name.clone()),
+            object => object.clone(),
+        }
+    }
+
+    fn class_scope(&self) -> &str {
+        self.namespace.last().map_or("#main", String::as_str)
+    }
+
+    fn define_method(&mut self, target: &Type, name: String, method: Method) {
+        let key = target.to_string();
+        let object = match target {
+            Type::Singleton(class) => self
+                .singletons
+                .entry(class.clone())
+                .or_insert_with(|| Type::sig([])),
+            _ => match self.modules.get_mut(&key) {
+                Some(module) => module,
+                None => self.objects.entry(key).or_insert_with(|| Type::sig([])),
+            },
+        };
+        if let Type::Signature(sig) = object {
+            sig.insert(name, method);
+        }
+    }
+
+    fn visit_block_scope<'pr>(&mut self, parameters: Option<Node<'pr>>, body: Option<Node<'pr>>) {
+        let stack_depth = self.type_stack.len();
+        self.local_variables.push(HashMap::new());
+        if let Some(parameters) = parameters {
+            self.visit(&parameters);
+        }
+        if let Some(body) = body {
+            self.visit(&body);
+        }
+        self.local_variables.pop();
+        self.type_stack.truncate(stack_depth);
+    }
+
+    /// Looks up a constant by its full name. Classes and modules resolve to
+    /// their singleton type; constants declared later in the file resolve to
+    /// `Object` until their value has been seen.
+    fn lookup_constant(&self, name: &str) -> Option<Type> {
+        if let Some(ty) = self.constants.get(name) {
+            Some(ty.clone())
+        } else if self.objects.contains_key(name) || self.modules.contains_key(name) {
+            Some(Type::singleton(name))
+        } else {
+            self.declared_constants.get(name).cloned()
+        }
+    }
+
+    /// Resolves `name` from the innermost namespace outwards, then among the
+    /// core constants.
+    fn resolve_constant(&self, name: &str) -> Option<Type> {
+        (0..=self.namespace.len())
+            .rev()
+            .find_map(|depth| {
+                self.lookup_constant(&nest(&self.namespace[..depth], name.to_string()))
+            })
+            .or_else(|| core_constant(name))
+    }
+
+    /// The value of a branch such as the body of an `if`: `NilClass` when it
+    /// is empty and `None` when it ends in `return` and so has no value.
+    fn branch_type<'pr>(
+        &mut self,
+        statements: Option<ruby_prism::StatementsNode<'pr>>,
+    ) -> Option<Type> {
+        let Some(statements) = statements else {
+            return Some(Type::alias("NilClass"));
+        };
+        let statements = statements.as_node();
+        let ty = self
+            .value_type(&statements)
+            .unwrap_or_else(|| Type::alias("Object"));
+        (!ends_with_return(&statements)).then_some(ty)
+    }
+
+    /// Pushes the union of the branches that produce a value, or nothing when
+    /// every branch returns.
+    fn push_branches<I>(&mut self, branches: I)
+    where
+        I: IntoIterator<Item = Option<Type>>,
+    {
+        let types = branches.into_iter().flatten().collect::<Vec<_>>();
+        if !types.is_empty() {
+            self.type_stack.push(Type::union(types));
+        }
+    }
+
+    /// The full name written to by `Foo::BAR = ...`. The target itself is
+    /// not resolved, since it may not exist yet.
+    fn constant_path_target<'pr>(
+        &mut self,
+        target: &ruby_prism::ConstantPathNode<'pr>,
+    ) -> Variable {
+        let name = target.name().map(to_string).unwrap_or_default();
+        let name = match target.parent() {
+            None => name,
+            Some(parent) => match self.value_type(&parent) {
+                Some(Type::Singleton(parent)) => format!("{}::{}", parent, name),
+                _ => qualify(&self.namespace, &target.as_node()),
+            },
+        };
+        Variable::Constant(name)
+    }
+
+    fn push_constant(&mut self, name: String, ty: Option<Type>, node: Node) {
+        match ty {
+            Some(ty) => self.type_stack.push(ty),
+            None => self
+                .errors
+                .push(TypeError::new(ErrorKind::UndefinedConstant(name), node)),
+        }
+    }
+}
+
+/// The written name of a class or module path such as `Foo::Bar`, and whether
+/// it is anchored at the top level with a leading `::`.
+fn constant_path_name(node: &Node) -> (bool, String) {
+    if let Some(path) = node.as_constant_path_node() {
+        let name = path.name().map(to_string).unwrap_or_default();
+        match path.parent() {
+            Some(parent) => {
+                let (anchored, parent) = constant_path_name(&parent);
+                (anchored, format!("{}::{}", parent, name))
+            }
+            None => (true, name),
+        }
+    } else if let Some(read) = node.as_constant_read_node() {
+        (false, to_string(read.name()))
+    } else {
+        (false, String::new())
+    }
+}
+
+/// The full name of a class or module defined at `constant_path` inside
+/// `namespace`, e.g. `A::B` for `class B` nested in `class A`.
+fn qualify(namespace: &[String], constant_path: &Node) -> String {
+    match constant_path_name(constant_path) {
+        (true, name) => name,
+        (false, name) => nest(namespace, name),
+    }
+}
+
+/// The full name of constant `name` defined directly inside `namespace`.
+fn nest(namespace: &[String], name: String) -> String {
+    match namespace.last() {
+        Some(outer) => format!("{}::{}", outer, name),
+        None => name,
+    }
+}
+
+/// Whether the last statement of a method body is an explicit `return`, in
+/// which case the body has no implicit value of its own.
+fn ends_with_return(body: &Node) -> bool {
+    body.as_statements_node()
+        .and_then(|statements| statements.body().iter().last())
+        .is_some_and(|stmt| stmt.as_return_node().is_some())
 }
 
 pub fn to_string(c: ruby_prism::ConstantId) -> String {
@@ -121,49 +915,192 @@ impl Default for TypeChecker {
 
 impl<'pr> Visit<'pr> for TypeChecker {
     fn visit_def_node(&mut self, node: &ruby_prism::DefNode<'pr>) {
+        let stack_depth = self.type_stack.len();
+        // Methods defined on other objects, as in `def obj.m`, are not tracked.
+        let target = match node.receiver() {
+            Some(receiver) if receiver.as_self_node().is_some() => Some(self.singleton_of_self()),
+            Some(receiver) => {
+                self.value_type(&receiver);
+                None
+            }
+            None => self.definees.last().unwrap().clone(),
+        };
+        let object = target.clone().unwrap_or_else(|| Type::alias("Object"));
+        self.object_stack.push(object);
         self.local_variables.push(HashMap::new());
-        for param in node.parameters().iter() {
-            self.visit(&param.as_node());
+        self.return_types.push(Vec::new());
+        // Snapshot the parameter types before the body can reassign them.
+        let mut args = Vec::new();
+        if let Some(parameters) = node.parameters() {
+            self.visit(&parameters.as_node());
+            for param in parameters.requireds().iter() {
+                let ty = param
+                    .as_required_parameter_node()
+                    .and_then(|param| {
+                        self.variable_type(&Variable::Local(to_string(param.name()), 0))
+                    })
+                    .unwrap_or_else(|| Type::alias("Object"));
+                args.push(ty);
+            }
+            self.type_stack.truncate(stack_depth);
         }
         if let Some(body) = node.body() {
             self.visit(&body);
         }
-        let args = self
-            .params
-            .iter()
-            .map(|param| {
-                self.local_variables
-                    .last()
-                    .unwrap()
-                    .get(param)
-                    .unwrap()
-                    .clone()
-            })
-            .collect::<Vec<_>>();
-        self.params.clear();
         self.local_variables.pop();
+        self.object_stack.pop();
 
-        if let Some(Type::Signature(sig)) = self.objects.get_mut("#main") {
-            sig.insert(
-                to_string(node.name()),
-                Method {
-                    args,
-                    ret: self.type_stack.pop().unwrap(),
-                },
-            );
+        let mut returns = self.return_types.pop().unwrap();
+        if !node.body().is_some_and(|body| ends_with_return(&body)) {
+            let implicit = match self.type_stack.len() - stack_depth {
+                0 => Type::alias("NilClass"),
+                1 => self.pop_type(),
+                _ => Type::alias("Object"),
+            };
+            returns.push(implicit);
         }
+        let ret = Type::union(returns);
+        self.type_stack.truncate(stack_depth);
+
+        if let Some(target) = target {
+            self.define_method(&target, to_string(node.name()), Method { args, ret });
+        }
+    }
+
+    fn visit_call_node(&mut self, node: &ruby_prism::CallNode<'pr>) {
+        let receiver = match node.receiver() {
+            Some(receiver) => self
+                .value_type(&receiver)
+                .unwrap_or_else(|| Type::alias("Object")),
+            None => self.object_stack.last().unwrap().clone(),
+        };
+        let arguments = node
+            .arguments()
+            .map(|arguments| arguments.arguments().iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut args = Vec::new();
+        for argument in arguments {
+            args.push((self.value_type(&argument), argument));
+        }
+        if let Some(block) = node.block() {
+            let stack_depth = self.type_stack.len();
+            self.visit(&block);
+            self.type_stack.truncate(stack_depth);
+        }
+
+        let ty = self
+            .call_method(&receiver, &to_string(node.name()), args)
+            .unwrap_or_else(|| Type::alias("Object"));
+        self.type_stack.push(ty);
     }
 
     fn visit_statements_node(&mut self, node: &ruby_prism::StatementsNode<'pr>) {
+        // Only the last statement's value is the value of the sequence.
+        let stack_depth = self.type_stack.len();
         for stmt in node.body().iter() {
+            self.type_stack.truncate(stack_depth);
             self.visit(&stmt);
         }
+        if self.type_stack.len() > stack_depth + 1 {
+            self.type_stack.truncate(stack_depth);
+            self.type_stack.push(Type::alias("Object"));
+        }
+    }
+
+    fn visit_if_node(&mut self, node: &ruby_prism::IfNode<'pr>) {
+        self.value_type(&node.predicate());
+        let then = self.branch_type(node.statements());
+        let otherwise = match node.subsequent() {
+            Some(subsequent) => match subsequent.as_else_node() {
+                Some(else_node) => self.branch_type(else_node.statements()),
+                // `elsif` is a nested `if`, which pushes nothing when every
+                // branch returns.
+                None => self.value_type(&subsequent),
+            },
+            None => Some(Type::alias("NilClass")),
+        };
+        self.push_branches([then, otherwise]);
+    }
+
+    fn visit_unless_node(&mut self, node: &ruby_prism::UnlessNode<'pr>) {
+        self.value_type(&node.predicate());
+        let then = self.branch_type(node.statements());
+        let otherwise = self.branch_type(node.else_clause().and_then(|node| node.statements()));
+        self.push_branches([then, otherwise]);
+    }
+
+    fn visit_case_node(&mut self, node: &ruby_prism::CaseNode<'pr>) {
+        if let Some(predicate) = node.predicate() {
+            self.value_type(&predicate);
+        }
+        let mut branches = Vec::new();
+        for condition in node.conditions().iter() {
+            let Some(when) = condition.as_when_node() else {
+                continue;
+            };
+            for condition in when.conditions().iter() {
+                self.value_type(&condition);
+            }
+            branches.push(self.branch_type(when.statements()));
+        }
+        branches.push(self.branch_type(node.else_clause().and_then(|node| node.statements())));
+        self.push_branches(branches);
+    }
+
+    fn visit_case_match_node(&mut self, node: &ruby_prism::CaseMatchNode<'pr>) {
+        if let Some(predicate) = node.predicate() {
+            self.value_type(&predicate);
+        }
+        let mut branches = Vec::new();
+        for condition in node.conditions().iter() {
+            let Some(in_node) = condition.as_in_node() else {
+                continue;
+            };
+            self.value_type(&in_node.pattern());
+            branches.push(self.branch_type(in_node.statements()));
+        }
+        branches.push(self.branch_type(node.else_clause().and_then(|node| node.statements())));
+        self.push_branches(branches);
+    }
+
+    fn visit_begin_node(&mut self, node: &ruby_prism::BeginNode<'pr>) {
+        let mut body = self.branch_type(node.statements());
+        let mut branches = Vec::new();
+        let mut rescue = node.rescue_clause();
+        while let Some(clause) = rescue {
+            for exception in clause.exceptions().iter() {
+                self.value_type(&exception);
+            }
+            if let Some(reference) = clause.reference() {
+                self.value_type(&reference);
+            }
+            branches.push(self.branch_type(clause.statements()));
+            rescue = clause.subsequent();
+        }
+        // The `else` clause runs after the body and supplies the value when
+        // nothing was rescued.
+        if let Some(else_clause) = node.else_clause() {
+            body = self.branch_type(else_clause.statements());
+        }
+        if let Some(statements) = node.ensure_clause().and_then(|node| node.statements()) {
+            self.value_type(&statements.as_node());
+        }
+        branches.insert(0, body);
+        self.push_branches(branches);
+    }
+
+    fn visit_rescue_modifier_node(&mut self, node: &ruby_prism::RescueModifierNode<'pr>) {
+        let expression = self.value_type(&node.expression());
+        let rescue = self.value_type(&node.rescue_expression());
+        self.push_branches(
+            [expression, rescue].map(|ty| ty.or_else(|| Some(Type::alias("Object")))),
+        );
     }
 
     fn visit_local_variable_read_node(&mut self, node: &ruby_prism::LocalVariableReadNode<'pr>) {
         let name = to_string(node.name());
-        if let Some(ty) = self.local_variables.last().unwrap().get(&name) {
-            self.type_stack.push(ty.clone());
+        if let Some(ty) = self.variable_type(&Variable::Local(name.clone(), node.depth())) {
+            self.type_stack.push(ty);
         } else {
             self.errors.push(TypeError::new(
                 ErrorKind::UndefinedVariable(name),
@@ -173,11 +1110,67 @@ impl<'pr> Visit<'pr> for TypeChecker {
     }
 
     fn visit_required_parameter_node(&mut self, node: &ruby_prism::RequiredParameterNode<'pr>) {
-        self.local_variables
-            .last_mut()
-            .unwrap()
-            .insert(to_string(node.name()), Type::alias("Object"));
-        self.params.push(to_string(node.name()));
+        self.bind_parameter(Some(node.name()), "Object");
+    }
+
+    fn visit_optional_parameter_node(&mut self, node: &ruby_prism::OptionalParameterNode<'pr>) {
+        self.value_type(&node.value());
+        self.bind_parameter(Some(node.name()), "Object");
+    }
+
+    fn visit_rest_parameter_node(&mut self, node: &ruby_prism::RestParameterNode<'pr>) {
+        self.bind_parameter(node.name(), "Array");
+    }
+
+    fn visit_required_keyword_parameter_node(
+        &mut self,
+        node: &ruby_prism::RequiredKeywordParameterNode<'pr>,
+    ) {
+        self.bind_parameter(Some(node.name()), "Object");
+    }
+
+    fn visit_optional_keyword_parameter_node(
+        &mut self,
+        node: &ruby_prism::OptionalKeywordParameterNode<'pr>,
+    ) {
+        self.value_type(&node.value());
+        self.bind_parameter(Some(node.name()), "Object");
+    }
+
+    fn visit_keyword_rest_parameter_node(
+        &mut self,
+        node: &ruby_prism::KeywordRestParameterNode<'pr>,
+    ) {
+        self.bind_parameter(node.name(), "Hash");
+    }
+
+    fn visit_block_parameter_node(&mut self, node: &ruby_prism::BlockParameterNode<'pr>) {
+        self.bind_parameter(node.name(), "Object");
+    }
+
+    fn visit_numbered_parameters_node(&mut self, node: &ruby_prism::NumberedParametersNode<'pr>) {
+        for number in 1..=node.maximum() {
+            self.set_variable(
+                Variable::Local(format!("_{}", number), 0),
+                Type::alias("Object"),
+            );
+        }
+    }
+
+    fn visit_block_local_variable_node(&mut self, node: &ruby_prism::BlockLocalVariableNode<'pr>) {
+        self.bind_parameter(Some(node.name()), "NilClass");
+    }
+
+    fn visit_block_node(&mut self, node: &ruby_prism::BlockNode<'pr>) {
+        self.visit_block_scope(node.parameters(), node.body());
+    }
+
+    fn visit_lambda_node(&mut self, node: &ruby_prism::LambdaNode<'pr>) {
+        // `return` inside a lambda returns from the lambda, not the method.
+        self.return_types.push(Vec::new());
+        self.visit_block_scope(node.parameters(), node.body());
+        self.return_types.pop();
+        self.type_stack.push(Type::alias("Proc"));
     }
 
     fn visit_string_node(&mut self, _: &ruby_prism::StringNode<'pr>) {
@@ -188,19 +1181,390 @@ impl<'pr> Visit<'pr> for TypeChecker {
         self.type_stack.push(Type::alias("Integer"));
     }
 
+    fn visit_nil_node(&mut self, _: &ruby_prism::NilNode<'pr>) {
+        self.type_stack.push(Type::alias("NilClass"));
+    }
+
+    fn visit_true_node(&mut self, _: &ruby_prism::TrueNode<'pr>) {
+        self.type_stack.push(Type::alias("TrueClass"));
+    }
+
+    fn visit_false_node(&mut self, _: &ruby_prism::FalseNode<'pr>) {
+        self.type_stack.push(Type::alias("FalseClass"));
+    }
+
+    fn visit_interpolated_string_node(&mut self, node: &ruby_prism::InterpolatedStringNode<'pr>) {
+        let stack_depth = self.type_stack.len();
+        for part in node.parts().iter() {
+            self.visit(&part);
+        }
+        self.type_stack.truncate(stack_depth);
+        self.type_stack.push(Type::alias("String"));
+    }
+
+    fn visit_array_node(&mut self, node: &ruby_prism::ArrayNode<'pr>) {
+        let stack_depth = self.type_stack.len();
+        for element in node.elements().iter() {
+            self.visit(&element);
+        }
+        self.type_stack.truncate(stack_depth);
+        self.type_stack.push(Type::alias("Array"));
+    }
+
+    fn visit_hash_node(&mut self, node: &ruby_prism::HashNode<'pr>) {
+        let stack_depth = self.type_stack.len();
+        for element in node.elements().iter() {
+            self.visit(&element);
+        }
+        self.type_stack.truncate(stack_depth);
+        self.type_stack.push(Type::alias("Hash"));
+    }
+
     fn visit_class_node(&mut self, node: &ruby_prism::ClassNode<'pr>) {
-        println!("class name: {:?}", node.name());
+        if let Some(superclass) = node.superclass() {
+            self.value_type(&superclass);
+        }
+        let name = qualify(&self.namespace, &node.constant_path());
+        self.objects
+            .entry(name.clone())
+            .or_insert_with(|| Type::sig([]));
+        self.visit_namespace_body(name, node.body());
+    }
+
+    fn visit_singleton_class_node(&mut self, node: &ruby_prism::SingletonClassNode<'pr>) {
+        // Only `class << self` is tracked; methods defined in it are singleton
+        // methods of the enclosing object.
+        let stack_depth = self.type_stack.len();
+        let expression = node.expression();
+        let (object, definee) = if expression.as_self_node().is_some() {
+            let object = self.singleton_of_self();
+            (object.clone(), Some(object))
+        } else {
+            self.value_type(&expression);
+            (Type::alias("Object"), None)
+        };
+        self.object_stack.push(object);
+        self.definees.push(definee);
+        self.local_variables.push(HashMap::new());
+        if let Some(body) = node.body() {
+            self.visit(&body);
+        }
+        self.local_variables.pop();
+        self.definees.pop();
+        self.object_stack.pop();
+        self.type_stack.truncate(stack_depth);
+    }
+
+    fn visit_module_node(&mut self, node: &ruby_prism::ModuleNode<'pr>) {
+        let name = qualify(&self.namespace, &node.constant_path());
+        self.modules
+            .entry(name.clone())
+            .or_insert_with(|| Type::sig([]));
+        self.visit_namespace_body(name, node.body());
     }
 
     fn visit_local_variable_write_node(&mut self, node: &ruby_prism::LocalVariableWriteNode<'pr>) {
-        self.visit(&node.value());
-        self.local_variables
-            .last_mut()
-            .unwrap()
-            .insert(to_string(node.name()), self.type_stack.pop().unwrap());
+        self.write_variable(
+            Variable::Local(to_string(node.name()), node.depth()),
+            &node.value(),
+        );
+    }
+
+    fn visit_local_variable_operator_write_node(
+        &mut self,
+        node: &ruby_prism::LocalVariableOperatorWriteNode<'pr>,
+    ) {
+        self.operator_write_variable(
+            Variable::Local(to_string(node.name()), node.depth()),
+            node.binary_operator(),
+            node.value(),
+        );
+    }
+
+    fn visit_local_variable_or_write_node(
+        &mut self,
+        node: &ruby_prism::LocalVariableOrWriteNode<'pr>,
+    ) {
+        self.or_write_variable(
+            Variable::Local(to_string(node.name()), node.depth()),
+            &node.value(),
+        );
+    }
+
+    fn visit_local_variable_and_write_node(
+        &mut self,
+        node: &ruby_prism::LocalVariableAndWriteNode<'pr>,
+    ) {
+        self.and_write_variable(
+            Variable::Local(to_string(node.name()), node.depth()),
+            &node.value(),
+        );
+    }
+
+    fn visit_local_variable_target_node(
+        &mut self,
+        node: &ruby_prism::LocalVariableTargetNode<'pr>,
+    ) {
+        let variable = Variable::Local(to_string(node.name()), node.depth());
+        self.set_variable(variable, Type::alias("Object"));
+    }
+
+    fn visit_instance_variable_target_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableTargetNode<'pr>,
+    ) {
+        let variable = Variable::Instance(to_string(node.name()));
+        self.set_variable(variable, Type::alias("Object"));
+    }
+
+    fn visit_class_variable_target_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableTargetNode<'pr>,
+    ) {
+        let variable = Variable::Class(to_string(node.name()));
+        self.set_variable(variable, Type::alias("Object"));
+    }
+
+    fn visit_global_variable_target_node(
+        &mut self,
+        node: &ruby_prism::GlobalVariableTargetNode<'pr>,
+    ) {
+        let variable = Variable::Global(to_string(node.name()));
+        self.set_variable(variable, Type::alias("Object"));
+    }
+
+    fn visit_instance_variable_read_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableReadNode<'pr>,
+    ) {
+        // The write may be in a method that has not been visited yet.
+        let ty = self
+            .variable_type(&Variable::Instance(to_string(node.name())))
+            .unwrap_or_else(|| Type::alias("Object"));
+        self.type_stack.push(ty);
+    }
+
+    fn visit_instance_variable_write_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableWriteNode<'pr>,
+    ) {
+        self.write_variable(Variable::Instance(to_string(node.name())), &node.value());
+    }
+
+    fn visit_instance_variable_operator_write_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableOperatorWriteNode<'pr>,
+    ) {
+        self.operator_write_variable(
+            Variable::Instance(to_string(node.name())),
+            node.binary_operator(),
+            node.value(),
+        );
+    }
+
+    fn visit_instance_variable_or_write_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableOrWriteNode<'pr>,
+    ) {
+        self.or_write_variable(Variable::Instance(to_string(node.name())), &node.value());
+    }
+
+    fn visit_instance_variable_and_write_node(
+        &mut self,
+        node: &ruby_prism::InstanceVariableAndWriteNode<'pr>,
+    ) {
+        self.and_write_variable(Variable::Instance(to_string(node.name())), &node.value());
+    }
+
+    fn visit_class_variable_read_node(&mut self, node: &ruby_prism::ClassVariableReadNode<'pr>) {
+        let name = to_string(node.name());
+        if let Some(ty) = self.variable_type(&Variable::Class(name.clone())) {
+            self.type_stack.push(ty);
+        } else if self
+            .declared_class_variables
+            .get(self.class_scope())
+            .is_some_and(|names| names.contains(&name))
+        {
+            // Written by code that has not been visited yet.
+            self.type_stack.push(Type::alias("Object"));
+        } else {
+            self.errors.push(TypeError::new(
+                ErrorKind::UndefinedVariable(name),
+                node.as_node(),
+            ));
+        }
+    }
+
+    fn visit_class_variable_write_node(&mut self, node: &ruby_prism::ClassVariableWriteNode<'pr>) {
+        self.write_variable(Variable::Class(to_string(node.name())), &node.value());
+    }
+
+    fn visit_class_variable_operator_write_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableOperatorWriteNode<'pr>,
+    ) {
+        self.operator_write_variable(
+            Variable::Class(to_string(node.name())),
+            node.binary_operator(),
+            node.value(),
+        );
+    }
+
+    fn visit_class_variable_or_write_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableOrWriteNode<'pr>,
+    ) {
+        self.or_write_variable(Variable::Class(to_string(node.name())), &node.value());
+    }
+
+    fn visit_class_variable_and_write_node(
+        &mut self,
+        node: &ruby_prism::ClassVariableAndWriteNode<'pr>,
+    ) {
+        self.and_write_variable(Variable::Class(to_string(node.name())), &node.value());
+    }
+
+    fn visit_global_variable_read_node(&mut self, node: &ruby_prism::GlobalVariableReadNode<'pr>) {
+        let ty = self
+            .variable_type(&Variable::Global(to_string(node.name())))
+            .unwrap_or_else(|| Type::alias("Object"));
+        self.type_stack.push(ty);
+    }
+
+    fn visit_global_variable_write_node(
+        &mut self,
+        node: &ruby_prism::GlobalVariableWriteNode<'pr>,
+    ) {
+        self.write_variable(Variable::Global(to_string(node.name())), &node.value());
+    }
+
+    fn visit_global_variable_operator_write_node(
+        &mut self,
+        node: &ruby_prism::GlobalVariableOperatorWriteNode<'pr>,
+    ) {
+        self.operator_write_variable(
+            Variable::Global(to_string(node.name())),
+            node.binary_operator(),
+            node.value(),
+        );
+    }
+
+    fn visit_global_variable_or_write_node(
+        &mut self,
+        node: &ruby_prism::GlobalVariableOrWriteNode<'pr>,
+    ) {
+        self.or_write_variable(Variable::Global(to_string(node.name())), &node.value());
+    }
+
+    fn visit_global_variable_and_write_node(
+        &mut self,
+        node: &ruby_prism::GlobalVariableAndWriteNode<'pr>,
+    ) {
+        self.and_write_variable(Variable::Global(to_string(node.name())), &node.value());
+    }
+
+    fn visit_program_node(&mut self, node: &ruby_prism::ProgramNode<'pr>) {
+        let mut declared = Declarations::default();
+        declared.visit_program_node(node);
+        self.declared_constants = declared.constants;
+        self.declared_class_variables = declared.class_variables;
+        ruby_prism::visit_program_node(self, node);
+    }
+
+    fn visit_constant_read_node(&mut self, node: &ruby_prism::ConstantReadNode<'pr>) {
+        let name = to_string(node.name());
+        let ty = self.resolve_constant(&name);
+        self.push_constant(name, ty, node.as_node());
+    }
+
+    fn visit_constant_path_node(&mut self, node: &ruby_prism::ConstantPathNode<'pr>) {
+        let name = node.name().map(to_string).unwrap_or_default();
+        match node.parent() {
+            // `::Foo` only looks at the top level.
+            None => {
+                let ty = self.lookup_constant(&name).or_else(|| core_constant(&name));
+                self.push_constant(name, ty, node.as_node());
+            }
+            Some(parent) => match self.value_type(&parent) {
+                Some(Type::Singleton(parent)) => {
+                    let name = format!("{}::{}", parent, name);
+                    // Constants nested in core classes, such as
+                    // `Float::INFINITY`, are not tracked individually.
+                    let ty = self.lookup_constant(&name).or_else(|| {
+                        CORE_CONSTANTS
+                            .contains(&parent.as_str())
+                            .then(|| Type::alias("Object"))
+                    });
+                    self.push_constant(name, ty, node.as_node());
+                }
+                // The parent is unknown or already reported as undefined.
+                _ => self.type_stack.push(Type::alias("Object")),
+            },
+        }
+    }
+
+    fn visit_constant_write_node(&mut self, node: &ruby_prism::ConstantWriteNode<'pr>) {
+        let name = nest(&self.namespace, to_string(node.name()));
+        self.write_variable(Variable::Constant(name), &node.value());
+    }
+
+    fn visit_constant_path_write_node(&mut self, node: &ruby_prism::ConstantPathWriteNode<'pr>) {
+        let variable = self.constant_path_target(&node.target());
+        self.write_variable(variable, &node.value());
+    }
+
+    fn visit_constant_path_operator_write_node(
+        &mut self,
+        node: &ruby_prism::ConstantPathOperatorWriteNode<'pr>,
+    ) {
+        let variable = self.constant_path_target(&node.target());
+        self.operator_write_variable(variable, node.binary_operator(), node.value());
+    }
+
+    fn visit_constant_path_or_write_node(
+        &mut self,
+        node: &ruby_prism::ConstantPathOrWriteNode<'pr>,
+    ) {
+        let variable = self.constant_path_target(&node.target());
+        self.or_write_variable(variable, &node.value());
+    }
+
+    fn visit_constant_path_and_write_node(
+        &mut self,
+        node: &ruby_prism::ConstantPathAndWriteNode<'pr>,
+    ) {
+        let variable = self.constant_path_target(&node.target());
+        self.and_write_variable(variable, &node.value());
+    }
+
+    fn visit_return_node(&mut self, node: &ruby_prism::ReturnNode<'pr>) {
+        let values = node
+            .arguments()
+            .map(|arguments| arguments.arguments().iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let ty = match values.as_slice() {
+            [] => Type::alias("NilClass"),
+            [value] => self
+                .value_type(value)
+                .unwrap_or_else(|| Type::alias("Object")),
+            values => {
+                for value in values {
+                    self.value_type(value);
+                }
+                Type::alias("Array")
+            }
+        };
+        // `return_types` only has an entry while a method body is visited.
+        match self.return_types.last_mut() {
+            Some(return_types) => return_types.push(ty),
+            None => self.errors.push(TypeError::new(
+                ErrorKind::ReturnOutsideMethod,
+                node.as_node(),
+            )),
+        }
     }
 
-    fn visit_symbol_node(&mut self, node: &ruby_prism::SymbolNode<'pr>) {
-        println!("Find symbol: {:?}", str::from_utf8(node.unescaped()))
+    fn visit_symbol_node(&mut self, _: &ruby_prism::SymbolNode<'pr>) {
+        self.type_stack.push(Type::alias("Symbol"));
     }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use ruby_analyzer::typecheck::{Method, Type, TypeChecker, TypeError};
+use ruby_analyzer::typecheck::{ErrorKind, Method, Type, TypeChecker, TypeError};
 use ruby_prism::{Node, ParseResult, Visit};
 use yaml_rust::YamlLoader;
 
@@ -11,12 +11,36 @@ pub struct Scenario<'pr> {
     pub errors: Vec<TypeError>,
 }
 
+fn parse_type(name: &str) -> Type {
+    Type::union(name.split(" | ").map(|name| {
+        match name
+            .strip_prefix("singleton(")
+            .and_then(|name| name.strip_suffix(')'))
+        {
+            Some(name) => Type::singleton(name),
+            None => Type::alias(name),
+        }
+    }))
+}
+
 fn run_scenario(ruby_node: Node, methods: HashMap<String, Method>, _errors: Vec<TypeError>) {
     let mut checker = TypeChecker::new();
     checker.visit(&ruby_node);
-    let object = checker.get_object("#main").unwrap().as_sig().unwrap();
     for (name, ty) in methods {
-        assert_eq!(object.get(&name), Some(&ty));
+        // `Foo#m` is an instance method, `Foo.m` a singleton method and a bare
+        // name a top-level method.
+        let (object, method) = if let Some((object, method)) = name.split_once('#') {
+            let object = checker
+                .get_object(object)
+                .or_else(|| checker.get_module(object));
+            (object, method)
+        } else if let Some((object, method)) = name.split_once('.') {
+            (checker.get_singleton(object), method)
+        } else {
+            (checker.get_object("#main"), name.as_str())
+        };
+        let object = object.unwrap().as_sig().unwrap();
+        assert_eq!(object.get(method), Some(&ty));
     }
 }
 
@@ -35,13 +59,371 @@ fn test_scenario() {
                     .as_vec()
                     .unwrap()
                     .iter()
-                    .map(|x| Type::alias(x.as_str().unwrap()))
+                    .map(|x| parse_type(x.as_str().unwrap()))
                     .collect();
                 (
                     k.as_str().unwrap().to_string(),
-                    Method::new(params, Type::alias(v["return"].as_str().unwrap())),
+                    Method::new(params, parse_type(v["return"].as_str().unwrap())),
                 )
             }));
         run_scenario(ruby_code, methods, vec![]);
     }
 }
+
+#[test]
+fn test_class_registered_as_object() {
+    let parse_result = ruby_prism::parse(b"class Foo\nend\n\nclass Bar < Foo\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    assert_eq!(checker.get_object("Foo"), Some(&Type::sig([])));
+    assert_eq!(checker.get_object("Bar"), Some(&Type::sig([])));
+}
+
+#[test]
+fn test_nested_class_registered_by_full_path() {
+    let parse_result = ruby_prism::parse(b"class A\n  class B\n  end\nend\n\nclass A::C\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    assert_eq!(checker.get_object("A"), Some(&Type::sig([])));
+    assert_eq!(checker.get_object("A::B"), Some(&Type::sig([])));
+    assert_eq!(checker.get_object("A::C"), Some(&Type::sig([])));
+    assert_eq!(checker.get_object("B"), None);
+    assert_eq!(checker.get_object("C"), None);
+}
+
+#[test]
+fn test_class_body_methods() {
+    let parse_result = ruby_prism::parse(b"class Foo\n  def bar\n    :bar\n  end\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    assert_eq!(
+        checker.get_object("Foo"),
+        Some(&Type::sig([(
+            "bar".to_string(),
+            Method::new(vec![], Type::alias("Symbol"))
+        )]))
+    );
+    assert_eq!(
+        checker
+            .get_object("#main")
+            .unwrap()
+            .as_sig()
+            .unwrap()
+            .get("bar"),
+        None
+    );
+}
+
+#[test]
+fn test_class_body_has_its_own_scope() {
+    let errors = TypeChecker::check("class Foo\n  x = 1\n  [1].each { |i| x }\nend\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_return_in_class_body() {
+    let parse_result = ruby_prism::parse(b"class Foo\n  return\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    let errors = checker.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind(), &ErrorKind::ReturnOutsideMethod);
+}
+
+#[test]
+fn test_module_registered_separately() {
+    let parse_result = ruby_prism::parse(b"module Helpers\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    assert_eq!(checker.get_module("Helpers"), Some(&Type::sig([])));
+    assert_eq!(checker.get_object("Helpers"), None);
+}
+
+#[test]
+fn test_module_with_method_definitions() {
+    let parse_result = ruby_prism::parse(b"module Helpers\n  def greet\n    \"hi\"\n  end\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    assert_eq!(
+        checker.get_module("Helpers"),
+        Some(&Type::sig([(
+            "greet".to_string(),
+            Method::new(vec![], Type::alias("String"))
+        )]))
+    );
+    assert_eq!(checker.get_object("Helpers"), None);
+}
+
+#[test]
+fn test_check_reports_errors_in_module_body() {
+    let errors = TypeChecker::check("module M\n  def f\n    x = 1 if x\n  end\nend\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedVariable("x".to_string())
+    );
+}
+
+#[test]
+fn test_check_reports_undefined_variable() {
+    // `x` in the modifier condition is read before the assignment runs.
+    let errors = TypeChecker::check("def foo\n  x = 1 if x\n  0\nend\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedVariable("x".to_string())
+    );
+}
+
+#[test]
+fn test_errors_expose_kind_and_location() {
+    let parse_result = ruby_prism::parse(b"def foo\n  x = 1 if x\n  0\nend\n");
+    let mut checker = TypeChecker::new();
+    checker.visit(&parse_result.node());
+    let errors = checker.errors();
+    assert_eq!(errors.len(), 1);
+    match errors[0].kind() {
+        ErrorKind::UndefinedVariable(name) => assert_eq!(name, "x"),
+        kind => panic!("unexpected error kind: {:?}", kind),
+    }
+    assert_eq!(errors[0].loc(), (19, 20));
+}
+
+#[test]
+fn test_class_variables_are_scoped_per_class() {
+    let errors = TypeChecker::check(
+        "class A\n  def set\n    @@n = 1\n  end\nend\n\nclass B\n  def get\n    @@n\n  end\nend\n",
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedVariable("@@n".to_string())
+    );
+}
+
+#[test]
+fn test_return_outside_method() {
+    let errors = TypeChecker::check("return 1\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind(), &ErrorKind::ReturnOutsideMethod);
+    assert_eq!(errors[0].loc(), (0, 8));
+}
+
+#[test]
+fn test_return_outside_method_checks_its_value() {
+    let errors = TypeChecker::check("return @@missing\n");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedVariable("@@missing".to_string())
+    );
+    assert_eq!(errors[1].kind(), &ErrorKind::ReturnOutsideMethod);
+}
+
+#[test]
+fn test_check_top_level_code() {
+    let errors = TypeChecker::check("x = 1\nx\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_check_reports_syntax_errors() {
+    let errors = TypeChecker::check("def foo(");
+    assert!(errors
+        .iter()
+        .any(|error| matches!(error.kind(), ErrorKind::SyntaxError(_))));
+}
+
+#[test]
+fn test_error_kind_display() {
+    let kind = ErrorKind::TypeMismatch {
+        expected: Type::alias("String"),
+        actual: Type::alias("Integer"),
+    };
+    assert_eq!(
+        kind.to_string(),
+        "type mismatch: expected String, found Integer"
+    );
+    assert_eq!(
+        ErrorKind::UndefinedVariable("x".to_string()).to_string(),
+        "undefined variable `x`"
+    );
+}
+
+#[test]
+fn test_error_line_column() {
+    let source = "def foo\n  x = \"é\" if x\n  0\nend\n";
+    let errors = TypeChecker::check(source);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].loc(), (22, 23));
+    assert_eq!(errors[0].line_column(source), (2, 14, 2, 15));
+}
+
+#[test]
+fn test_error_line_column_counts_multibyte_characters() {
+    let source = "x = \"é\"\nreturn x +\n  \"é\"\n";
+    let errors = TypeChecker::check(source);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].loc(), (9, 26));
+    // `é` is two bytes but one column.
+    assert_eq!(errors[0].line_column(source), (2, 1, 3, 6));
+}
+
+#[test]
+fn test_argument_type_mismatch() {
+    let errors = TypeChecker::check("def foo\n  \"a\" + 1\nend\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::TypeMismatch {
+            expected: Type::alias("String"),
+            actual: Type::alias("Integer"),
+        }
+    );
+    assert_eq!(errors[0].loc(), (16, 17));
+}
+
+#[test]
+fn test_argument_of_unknown_type_is_not_checked() {
+    let errors = TypeChecker::check("def greet(name)\n  \"Hi \" + name\nend\n");
+    assert!(errors.is_empty());
+    let errors = TypeChecker::check("def foo(x)\n  \"a\" + x.to_s\nend\n");
+    assert!(errors.is_empty());
+    // Float literals are not typed yet, so no mismatch can be claimed.
+    let errors = TypeChecker::check("def foo\n  \"a\" + 1.5\nend\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_interpolated_string_argument() {
+    let errors = TypeChecker::check("def foo\n  n = 1\n  \"a\" + \"n=#{n}\"\nend\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_operator_assignment_type_mismatch() {
+    let errors = TypeChecker::check("def foo\n  x = \"a\"\n  x += 1\nend\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::TypeMismatch {
+            expected: Type::alias("String"),
+            actual: Type::alias("Integer"),
+        }
+    );
+    assert_eq!(errors[0].loc(), (25, 26));
+}
+
+#[test]
+fn test_undefined_constant() {
+    let errors = TypeChecker::check("Missing\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedConstant("Missing".to_string())
+    );
+}
+
+#[test]
+fn test_core_constants_and_forward_references() {
+    let errors = TypeChecker::check(
+        "Integer\nArray\nHash\nStandardError\nComparable\nLater\n\nclass Later\nend\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_constant_path_does_not_resolve_by_last_segment() {
+    let errors = TypeChecker::check("class Bar\nend\n\nmodule Foo\nend\n\nFoo::Bar\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind(),
+        &ErrorKind::UndefinedConstant("Foo::Bar".to_string())
+    );
+}
+
+#[test]
+fn test_constants_nested_in_core_classes() {
+    let errors = TypeChecker::check(
+        "Float::INFINITY\nFile::SEPARATOR\nEncoding::UTF_8\n\nbegin\nrescue Errno::ENOENT\nend\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_core_value_constants() {
+    let errors = TypeChecker::check(
+        "def foo\n  \"Ruby \" + RUBY_VERSION + RUBY_PLATFORM\nend\n\nbegin\nrescue SystemCallError, EOFError\nend\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_constant_path_write() {
+    let errors = TypeChecker::check(
+        "def read_later\n  Foo::LATER\nend\n\nclass Foo\nend\n\nFoo::BAR = 1\nFoo::BAR\nFoo::LATER = 2\nFoo::CACHE ||= 3\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_branches_do_not_cause_false_mismatch() {
+    let errors =
+        TypeChecker::check("def f(x)\n  if x then \"a\" else 1 end\nend\n\n\"s\" + f(1)\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_all_parameter_kinds_are_bound() {
+    let errors = TypeChecker::check(
+        "def a(x = 1)\n  x\nend\n\ndef b(k:, o: 1)\n  [k, o]\nend\n\ndef c(*r, **kw, &blk)\n  [r, kw, blk]\nend\n\n[1].each { |*args| args }\n[1].each { _1 }\n[1].each { |x; y| y }\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_check_binds_assignment_targets() {
+    let errors = TypeChecker::check(
+        "a, b = 1, 2\n[a, b]\n\nbegin\nrescue Errno::ENOENT => e\n  e\nend\n\nfor i in [1]\n  i\nend\n\n@x, @@y, $z = 1, 2, 3\n@@y\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_instance_variable_read_before_write() {
+    let errors = TypeChecker::check(
+        "class Person\n  def greet\n    \"hi \" + @name\n  end\n\n  def initialize(name)\n    @name = name\n  end\nend\n",
+    );
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_builtin_globals() {
+    let errors = TypeChecker::check("def usage\n  \"prog: \" + $0 + $PROGRAM_NAME\nend\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_methods_on_other_objects_are_not_registered() {
+    let mut checker = TypeChecker::new();
+    let parse_result = ruby_prism::parse(
+        b"class Foo\n  def self.a\n    1\n  end\nend\n\nobj = Object.new\ndef obj.b\n  1\nend\n\nclass << obj\n  def c\n    1\n  end\nend\n",
+    );
+    checker.visit(&parse_result.node());
+    let singleton = checker.get_singleton("Foo").unwrap().as_sig().unwrap();
+    assert!(singleton.get("a").is_some());
+    assert!(checker.get_object("singleton(Foo)").is_none());
+    let main = checker.get_object("#main").unwrap().as_sig().unwrap();
+    assert!(main.get("b").is_none());
+    assert!(main.get("c").is_none());
+}
+
+#[test]
+fn test_class_variable_read_before_write() {
+    let errors = TypeChecker::check(
+        "class C\n  def get\n    @@n\n  end\n\n  def set\n    @@n = 1\n  end\nend\n",
+    );
+    assert!(errors.is_empty());
+    let errors = TypeChecker::check("class D\n  def get\n    @@n\n  end\nend\n");
+    assert_eq!(errors.len(), 1);
+}